@@ -1,35 +1,314 @@
-use console_api::tasks::{tasks_client::TasksClient, TasksRequest};
-use futures::stream::StreamExt;
+use console_api::tasks::{tasks_client::TasksClient, TaskUpdate, TasksRequest};
+use futures::stream::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_stream::StreamMap;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+use tonic::Streaming;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut args = std::env::args();
     args.next(); // drop the first arg (the name of the binary)
-    let target = args.next().unwrap_or_else(|| {
+
+    let mut targets = Vec::new();
+    let mut ca: Option<String> = None;
+    let mut client_cert: Option<String> = None;
+    let mut client_key: Option<String> = None;
+    let mut batch_size: Option<usize> = None;
+    let mut batch_millis: Option<u64> = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ca" => ca = args.next(),
+            "--client-cert" => client_cert = args.next(),
+            "--client-key" => client_key = args.next(),
+            "--batch-size" => {
+                let value = args.next().ok_or("--batch-size requires a value")?;
+                batch_size = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid --batch-size value: {}", value))?,
+                );
+            }
+            "--batch-millis" => {
+                let value = args.next().ok_or("--batch-millis requires a value")?;
+                batch_millis = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid --batch-millis value: {}", value))?,
+                );
+            }
+            _ => targets.push(arg),
+        }
+    }
+    let targets = if targets.is_empty() {
         eprintln!("using default address (http://127.0.0.1:6669)");
-        String::from("http://127.0.0.1:6669")
-    });
+        vec![String::from("http://127.0.0.1:6669")]
+    } else {
+        targets
+    };
 
-    eprintln!("CONNECTING: {}", target);
-    let mut client = TasksClient::connect(target).await?;
+    // `--ca`/`--client-cert`/`--client-key` take precedence over the
+    // CONSOLE_CA_CERT/CONSOLE_CLIENT_CERT/CONSOLE_CLIENT_KEY env vars, which
+    // remain supported since the TLS material applies to every target and is
+    // often more convenient to set once in the environment.
+    let ca = ca.or_else(|| std::env::var("CONSOLE_CA_CERT").ok());
+    let client_cert = client_cert.or_else(|| std::env::var("CONSOLE_CLIENT_CERT").ok());
+    let client_key = client_key.or_else(|| std::env::var("CONSOLE_CLIENT_KEY").ok());
 
-    let request = tonic::Request::new(TasksRequest {});
-    let mut stream = client.watch_tasks(request).await?.into_inner();
+    // Validate once up front: a mismatched cert/key is a permanent
+    // misconfiguration, not a transient failure, so it shouldn't be retried
+    // forever inside each target's reconnect loop.
+    if client_cert.is_some() != client_key.is_some() {
+        return Err("client certificate and client key must be supplied together".into());
+    }
+
+    let batch_size: usize = batch_size
+        .or_else(|| {
+            std::env::var("CONSOLE_BATCH_SIZE")
+                .ok()
+                .and_then(|n| n.parse().ok())
+        })
+        .unwrap_or(50);
+    let batch_duration: Duration = batch_millis
+        .map(Duration::from_millis)
+        .or_else(|| {
+            std::env::var("CONSOLE_BATCH_MILLIS")
+                .ok()
+                .and_then(|ms| ms.parse().ok())
+                .map(Duration::from_millis)
+        })
+        .unwrap_or_else(|| Duration::from_millis(250));
+
+    let mut updates = StreamMap::new();
+    for target in targets {
+        let watcher = watch_target(
+            target.clone(),
+            ca.clone(),
+            client_cert.clone(),
+            client_key.clone(),
+        );
+        updates.insert(target, Box::pin(watcher));
+    }
 
+    let start = Instant::now();
     let mut i: usize = 0;
-    while let Some(update) = stream.next().await {
-        match update {
-            Ok(update) => {
-                eprintln!("UPDATE {}: {:#?}\n", i, update);
-                i += 1;
+    let mut per_target: HashMap<String, usize> = HashMap::new();
+    let mut batch: Vec<(String, TaskUpdate)> = Vec::with_capacity(batch_size);
+    let sleep = tokio::time::sleep(batch_duration);
+    tokio::pin!(sleep);
+    let mut armed = false;
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            item = updates.next() => match item {
+                Some((source, update)) => {
+                    *per_target.entry(source.clone()).or_insert(0) += 1;
+                    if batch.is_empty() {
+                        sleep.as_mut().reset(tokio::time::Instant::now() + batch_duration);
+                        armed = true;
+                    }
+                    batch.push((source, update));
+                    if batch.len() >= batch_size {
+                        flush(&mut i, &mut batch);
+                        armed = false;
+                    }
+                }
+                None => break,
+            },
+            _ = &mut sleep, if armed => {
+                flush(&mut i, &mut batch);
+                armed = false;
             }
-            Err(e) => {
-                eprintln!("update stream error: {}", e);
-                return Err(e.into());
+            _ = &mut shutdown => {
+                eprintln!("shutting down...");
+                break;
             }
         }
     }
 
-    eprintln!("update stream terminated");
+    // Drop the gRPC streams before printing the summary.
+    drop(updates);
+
+    if !batch.is_empty() {
+        flush(&mut i, &mut batch);
+    }
+
+    eprintln!("--- summary ---");
+    eprintln!("total updates: {}", i);
+    eprintln!("elapsed: {:?}", start.elapsed());
+    for (target, count) in &per_target {
+        eprintln!("  {}: {}", target, count);
+    }
+
     Ok(())
 }
+
+/// Resolves when the process receives Ctrl-C, or SIGTERM on unix platforms.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Builds a (optionally TLS/mTLS) `TasksClient` for `target`.
+async fn connect(
+    target: &str,
+    ca: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+) -> Result<TasksClient<Channel>, Box<dyn std::error::Error>> {
+    eprintln!("CONNECTING: {}", target);
+    if ca.is_some() || client_cert.is_some() {
+        let domain_name = tonic::transport::Uri::try_from(target)?
+            .host()
+            .unwrap_or("localhost")
+            .to_string();
+        let mut tls = ClientTlsConfig::new().domain_name(domain_name);
+
+        if let Some(ca) = ca {
+            let ca_pem = std::fs::read(ca)?;
+            tls = tls.ca_certificate(Certificate::from_pem(ca_pem));
+        }
+
+        if let (Some(cert), Some(key)) = (client_cert, client_key) {
+            let cert_pem = std::fs::read(cert)?;
+            let key_pem = std::fs::read(key)?;
+            tls = tls.identity(Identity::from_pem(cert_pem, key_pem));
+        }
+
+        let channel = Channel::from_shared(target.to_string())?
+            .tls_config(tls)?
+            .connect()
+            .await?;
+        Ok(TasksClient::new(channel))
+    } else {
+        Ok(TasksClient::connect(target.to_string()).await?)
+    }
+}
+
+/// Yields a never-ending stream of `TaskUpdate`s for `target`, transparently
+/// reconnecting with exponential backoff whenever the connection or stream
+/// fails. Errors are logged and retried rather than propagated, so one dead
+/// target never stops the others sharing a `StreamMap`.
+fn watch_target(
+    target: String,
+    ca: Option<String>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+) -> impl Stream<Item = TaskUpdate> {
+    struct State {
+        target: String,
+        ca: Option<String>,
+        client_cert: Option<String>,
+        client_key: Option<String>,
+        stream: Option<Streaming<TaskUpdate>>,
+        backoff: Duration,
+    }
+
+    impl State {
+        /// Sleeps for the current (jittered) backoff, then doubles it up to `MAX_BACKOFF`.
+        async fn back_off(&mut self) {
+            tokio::time::sleep(jittered(self.backoff)).await;
+            self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    let init = State {
+        target,
+        ca,
+        client_cert,
+        client_key,
+        stream: None,
+        backoff: INITIAL_BACKOFF,
+    };
+
+    futures::stream::unfold(init, |mut st| async move {
+        loop {
+            if st.stream.is_none() {
+                let client = connect(
+                    &st.target,
+                    st.ca.clone(),
+                    st.client_cert.clone(),
+                    st.client_key.clone(),
+                )
+                .await;
+                let mut client = match client {
+                    Ok(client) => client,
+                    Err(e) => {
+                        eprintln!("[{}] connect error: {}", st.target, e);
+                        st.back_off().await;
+                        continue;
+                    }
+                };
+
+                match client.watch_tasks(tonic::Request::new(TasksRequest {})).await {
+                    Ok(resp) => {
+                        st.stream = Some(resp.into_inner());
+                        st.backoff = INITIAL_BACKOFF;
+                    }
+                    Err(e) => {
+                        eprintln!("[{}] watch_tasks error: {}", st.target, e);
+                        st.back_off().await;
+                        continue;
+                    }
+                }
+            }
+
+            match st.stream.as_mut().unwrap().next().await {
+                Some(Ok(update)) => return Some((update, st)),
+                Some(Err(e)) => {
+                    eprintln!("[{}] stream error: {}", st.target, e);
+                    st.stream = None;
+                    st.back_off().await;
+                }
+                None => {
+                    eprintln!("[{}] stream terminated, reconnecting", st.target);
+                    st.stream = None;
+                    st.back_off().await;
+                }
+            }
+        }
+    })
+}
+
+/// Prints a batch of `(source, update)` pairs and clears it, numbering each
+/// with the running total `i`.
+fn flush(i: &mut usize, batch: &mut Vec<(String, TaskUpdate)>) {
+    for (source, update) in batch.drain(..) {
+        eprintln!("UPDATE {} [{}]: {:#?}\n", i, source, update);
+        *i += 1;
+    }
+}
+
+/// Adds a small jitter to `base` so that reconnecting clients don't all retry in lockstep.
+fn jittered(base: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    base + Duration::from_millis((nanos % 250) as u64)
+}